@@ -0,0 +1,133 @@
+use crate::{SQLxSessionData, SessionError};
+#[cfg(feature = "bincode")]
+use std::collections::HashMap;
+
+/// Deturmines the format Sessions are Serialized to before being Saved to the Database.
+/// The binary formats are smaller and faster but require their cargo feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SQLxSessionCodec {
+    /// Saved as UTF-8 JSON so the Session can still be read within the Database.
+    Json,
+    /// Saved as MessagePack.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    /// Saved as CBOR.
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// Saved as bincode, the most compact but not self describing.
+    #[cfg(feature = "bincode")]
+    Bincode,
+}
+
+impl SQLxSessionCodec {
+    /// Serializes the Session into the Database payload.
+    pub(crate) fn encode(&self, session: &SQLxSessionData) -> Result<Vec<u8>, SessionError> {
+        Ok(match self {
+            SQLxSessionCodec::Json => serde_json::to_vec(session)?,
+            #[cfg(feature = "msgpack")]
+            SQLxSessionCodec::MessagePack => rmp_serde::to_vec_named(session)?,
+            #[cfg(feature = "cbor")]
+            SQLxSessionCodec::Cbor => {
+                let mut bytes = Vec::new();
+                ciborium::ser::into_writer(session, &mut bytes)?;
+                bytes
+            }
+            #[cfg(feature = "bincode")]
+            SQLxSessionCodec::Bincode => {
+                // bincode is not self describing so it can not Deserialize a serde_json Value,
+                // the values are Saved as JSON strings after the Session instead.
+                let values: HashMap<&String, String> = session
+                    .data
+                    .iter()
+                    .map(|(key, value)| (key, value.to_string()))
+                    .collect();
+                let session = SQLxSessionData {
+                    data: HashMap::new(),
+                    ..session.clone()
+                };
+
+                bincode::serialize(&(session, values))?
+            }
+        })
+    }
+
+    /// Deserializes the Session from the Database payload.
+    pub(crate) fn decode(&self, bytes: &[u8]) -> Result<SQLxSessionData, SessionError> {
+        Ok(match self {
+            SQLxSessionCodec::Json => serde_json::from_slice(bytes)?,
+            #[cfg(feature = "msgpack")]
+            SQLxSessionCodec::MessagePack => rmp_serde::from_slice(bytes)?,
+            #[cfg(feature = "cbor")]
+            SQLxSessionCodec::Cbor => ciborium::de::from_reader(bytes)?,
+            #[cfg(feature = "bincode")]
+            SQLxSessionCodec::Bincode => {
+                let (mut session, values): (SQLxSessionData, HashMap<String, String>) =
+                    bincode::deserialize(bytes)?;
+
+                for (key, value) in values {
+                    session.data.insert(key, serde_json::from_str(&value)?);
+                }
+
+                session
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SqlxSessionConfig;
+    use serde_json::json;
+
+    fn round_trip(codec: SQLxSessionCodec) {
+        let mut session = SQLxSessionData::new("id".into(), &SqlxSessionConfig::default());
+        session.persistent = true;
+        session.custom_lifespan = Some(60);
+        session.data.insert("string".into(), json!("value"));
+        session.data.insert("number".into(), json!(42));
+        session.data.insert("float".into(), json!(1.5));
+        session.data.insert("bool".into(), json!(true));
+        session.data.insert("null".into(), json!(null));
+        session.data.insert("list".into(), json!([1, "two", [3]]));
+        session
+            .data
+            .insert("object".into(), json!({"user": {"id": 7}}));
+
+        let bytes = codec.encode(&session).unwrap();
+        let decoded = codec.decode(&bytes).unwrap();
+
+        assert_eq!(decoded.data, session.data);
+        assert_eq!(decoded.expires, session.expires);
+        assert_eq!(decoded.autoremove, session.autoremove);
+        assert_eq!(decoded.created_at, session.created_at);
+        assert_eq!(decoded.persistent, session.persistent);
+        assert_eq!(decoded.custom_lifespan, session.custom_lifespan);
+        assert_eq!(decoded.fixed_expiry, session.fixed_expiry);
+        assert_eq!(decoded.destroy, session.destroy);
+        assert_eq!(decoded.format, session.format);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        round_trip(SQLxSessionCodec::Json);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_round_trip() {
+        round_trip(SQLxSessionCodec::MessagePack);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_round_trip() {
+        round_trip(SQLxSessionCodec::Cbor);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_round_trip() {
+        round_trip(SQLxSessionCodec::Bincode);
+    }
+}
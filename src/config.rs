@@ -1,15 +1,58 @@
+use crate::session_id::{MAX_ID_LEN, MIN_ID_LEN};
+use crate::SQLxSessionCodec;
 use chrono::Duration;
+use cookie::Key;
+use cookie::SameSite;
+use http::HeaderName;
+use std::fmt;
+
+/// Deturmines how the Session ID is sent between the client and the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SQLxSessionTransport {
+    /// The Session ID is kept within a cookie.
+    Cookie,
+    /// The Session ID is read from and written to the Session header, for clients without cookies.
+    Header,
+    /// The Session header is used when sent otherwise the cookie. Both are written.
+    CookieAndHeader,
+}
+
+/// Holds a cookie Key so the config can still be Debug printed without leaking the secret.
+#[derive(Clone)]
+pub(crate) struct SecretKey(pub(crate) Key);
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
 
 ///This is the Sessions Config it is used to Setup the SQL database and sets the hashmap saved Memory and Session life spans.
 #[derive(Debug, Clone)]
 pub struct SqlxSessionConfig {
     /// Sessions lifespan
     pub(crate) lifespan: Duration,
+    /// Persistent Sessions lifespan, used for "remember me" Sessions
+    pub(crate) persistent_lifespan: Duration,
+    /// Sessions absolute lifespan from creation, they Expire after it regardless of activity.
+    pub(crate) absolute_lifespan: Option<Duration>,
     /// Session cookie name
     pub(crate) cookie_name: String,
     /// Session cookie path
     pub(crate) cookie_path: String,
-    /// Session ID character length
+    /// Session cookie domain, when None the browser uses the requests host.
+    pub(crate) cookie_domain: Option<String>,
+    /// Session cookie is only sent over HTTPS
+    pub(crate) cookie_secure: bool,
+    /// Session cookie can not be read by Javascript
+    pub(crate) cookie_http_only: bool,
+    /// Session cookie SameSite policy
+    pub(crate) cookie_same_site: SameSite,
+    /// How the Session ID is sent between the client and the server
+    pub(crate) transport: SQLxSessionTransport,
+    /// Session header name used when the transport includes the header
+    pub(crate) header_name: HeaderName,
+    /// Session ID character length, each character carries 6 bits of entropy.
     pub(crate) cookie_len: usize,
     /// Session Database name
     pub(crate) database: String,
@@ -21,14 +64,32 @@ pub struct SqlxSessionConfig {
     pub(crate) host: String,
     /// Session Database Port address
     pub(crate) port: u16,
+    /// Store a SHA-256 hash of the Session ID within the Database instead of the ID itself
+    pub(crate) hash_ids: bool,
     /// Session Database table name default is async_sessions
     pub(crate) table_name: String,
+    /// Format Sessions are Serialized to within the Database
+    pub(crate) codec: SQLxSessionCodec,
     /// Session Database Max Poll Connections. Can not be 0
     pub(crate) max_connections: u32,
+    /// New Sessions are only Saved and given a cookie once data is set within them.
+    pub(crate) lazy_sessions: bool,
     /// Session Memory lifespan, deturmines when to unload it from memory
     /// this works fine since the data can stay in the database till its needed
     /// if not yet expired.
     pub(crate) memory_lifespan: Duration,
+    /// How far a Session's expiry must move before it is Saved again,
+    /// unchanged Sessions are otherwise not written back to the Database.
+    pub(crate) expiry_refresh: Duration,
+    /// How often the background sweeper removes expired Sessions. Once the sweeper is
+    /// running Sessions are no longer swept during requests.
+    pub(crate) sweep_interval: Option<Duration>,
+    /// Key used to Sign the Session cookie so tampered cookies are rejected.
+    pub(crate) signing_key: Option<SecretKey>,
+    /// Key used to Encrypt the Session cookie, takes priority over the signing key.
+    pub(crate) private_key: Option<SecretKey>,
+    /// Rotated out keys that are still accepted for Decrypting the Session cookie.
+    pub(crate) old_private_keys: Vec<SecretKey>,
 }
 
 impl SqlxSessionConfig {
@@ -49,6 +110,24 @@ impl SqlxSessionConfig {
         self
     }
 
+    /// Set the lifetime of persistent sessions, e.g. "remember me" logins.
+    /// Their cookie's Max-Age is set to this lifetime.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_persistent_lifetime(mut self, time: Duration) -> Self {
+        self.persistent_lifespan = time;
+        self
+    }
+
+    /// Set the absolute session lifetime. Sessions expire this long after being created
+    /// even if they are still in use.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_absolute_lifetime(mut self, time: Duration) -> Self {
+        self.absolute_lifespan = Some(time);
+        self
+    }
+
     /// Set session lifetime (expiration time) within Memory storage.
     ///
     /// Call on the fairing before passing it to `rocket.attach()`
@@ -57,6 +136,24 @@ impl SqlxSessionConfig {
         self
     }
 
+    /// Set how far the session expiry must be extended before an unchanged session
+    /// is saved to the database again.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_expiry_refresh(mut self, time: Duration) -> Self {
+        self.expiry_refresh = time;
+        self
+    }
+
+    /// Set the interval the background sweeper runs at. The sweeper must be started
+    /// with `SqlxSessionLayer::spawn_sweeper`. Can not be less than 1 second.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_sweep_interval(mut self, time: Duration) -> Self {
+        self.sweep_interval = Some(std::cmp::max(time, Duration::seconds(1)));
+        self
+    }
+
     /// Set session cookie name
     ///
     /// Call on the fairing before passing it to `rocket.attach()`
@@ -65,11 +162,12 @@ impl SqlxSessionConfig {
         self
     }
 
-    /// Set session cookie length
+    /// Set session ID length. Can not be less than 22 so IDs have at least 128 bits of entropy
+    /// or more than 128 so they fit within the Database.
     ///
     /// Call on the fairing before passing it to `rocket.attach()`
     pub fn with_cookie_len(mut self, length: usize) -> Self {
-        self.cookie_len = length;
+        self.cookie_len = length.clamp(MIN_ID_LEN, MAX_ID_LEN);
         self
     }
 
@@ -81,6 +179,81 @@ impl SqlxSessionConfig {
         self
     }
 
+    /// Set the key used to sign the session cookie. Cookies that fail to verify
+    /// are treated as missing and a new session is created.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_signing_key(mut self, key: Key) -> Self {
+        self.signing_key = Some(SecretKey(key));
+        self
+    }
+
+    /// Set the key used to encrypt the session cookie so its value is opaque to clients.
+    /// This also authenticates the cookie so a signing key is not needed.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_private_key(mut self, key: Key) -> Self {
+        self.private_key = Some(SecretKey(key));
+        self
+    }
+
+    /// Set previous private keys that are still accepted for decrypting the session cookie.
+    /// Cookies decrypted with one of these are re-issued using the current private key.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_old_private_keys(mut self, keys: Vec<Key>) -> Self {
+        self.old_private_keys = keys.into_iter().map(SecretKey).collect();
+        self
+    }
+
+    /// Set how the session ID is sent between the client and the server.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_transport(mut self, transport: SQLxSessionTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Set the session header name. When set to `Authorization` the ID is sent as a Bearer token.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_header_name(mut self, name: HeaderName) -> Self {
+        self.header_name = name;
+        self
+    }
+
+    /// Set session cookie domain
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_cookie_domain(mut self, domain: &str) -> Self {
+        self.cookie_domain = Some(domain.into());
+        self
+    }
+
+    /// Set if the session cookie is only sent over HTTPS
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.cookie_secure = secure;
+        self
+    }
+
+    /// Set if the session cookie is hidden from Javascript
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_http_only(mut self, http_only: bool) -> Self {
+        self.cookie_http_only = http_only;
+        self
+    }
+
+    /// Set the session cookie SameSite policy
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_same_site(mut self, same_site: SameSite) -> Self {
+        self.cookie_same_site = same_site;
+        self
+    }
+
     /// Set session database name
     ///
     /// Call on the fairing before passing it to `rocket.attach()`
@@ -105,6 +278,24 @@ impl SqlxSessionConfig {
         self
     }
 
+    /// Set if session IDs are hashed before being used as the database key.
+    /// Changing this invalidates all existing sessions.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_hashed_ids(mut self, hash_ids: bool) -> Self {
+        self.hash_ids = hash_ids;
+        self
+    }
+
+    /// Set if new sessions are only saved and given a cookie once data is set within them.
+    /// This stops bots and health checks from filling the database with empty sessions.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_lazy_sessions(mut self, lazy: bool) -> Self {
+        self.lazy_sessions = lazy;
+        self
+    }
+
     /// Set session database table name
     ///
     /// Call on the fairing before passing it to `rocket.attach()`
@@ -113,6 +304,15 @@ impl SqlxSessionConfig {
         self
     }
 
+    /// Set the format sessions are serialized to within the database.
+    /// Changing this invalidates all existing sessions.
+    ///
+    /// Call on the fairing before passing it to `rocket.attach()`
+    pub fn with_codec(mut self, codec: SQLxSessionCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
     /// Set session database hostname
     ///
     /// Call on the fairing before passing it to `rocket.attach()`
@@ -133,20 +333,39 @@ impl SqlxSessionConfig {
 impl Default for SqlxSessionConfig {
     fn default() -> Self {
         Self {
-            /// Set to 6hour for default in Database Session stores.
+            // Set to 6hour for default in Database Session stores.
             lifespan: Duration::hours(6),
+            // Remember me Sessions last 30 days.
+            persistent_lifespan: Duration::days(30),
+            absolute_lifespan: None,
             cookie_name: "sqlx_session".into(),
             cookie_path: "/".into(),
-            cookie_len: 16,
+            cookie_domain: None,
+            cookie_secure: false,
+            cookie_http_only: true,
+            cookie_same_site: SameSite::Lax,
+            transport: SQLxSessionTransport::Cookie,
+            header_name: HeaderName::from_static("x-session-id"),
+            // 32 characters gives 192 bits of entropy.
+            cookie_len: 32,
             database: "".into(),
             username: "".into(),
             password: "".into(),
             host: "localhost".into(),
             port: 5432,
+            hash_ids: false,
             table_name: "async_sessions".into(),
+            codec: SQLxSessionCodec::Json,
             max_connections: 5,
-            /// Unload memory after 60mins if it has not been accessed.
+            lazy_sessions: false,
+            // Unload memory after 60mins if it has not been accessed.
             memory_lifespan: Duration::minutes(60),
+            // Only save unchanged sessions once their expiry moved by 5mins.
+            expiry_refresh: Duration::minutes(5),
+            sweep_interval: None,
+            signing_key: None,
+            private_key: None,
+            old_private_keys: Vec::new(),
         }
     }
 }
@@ -0,0 +1,48 @@
+use crate::SessionError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::fmt::Debug;
+
+/// This is the Storage backend used by the SQLxSessionStore to persist Sessions.
+/// Implement it to add support for another Database. Each call is given the table name
+/// from the config so a single pool can serve more than one Session Store.
+#[async_trait]
+pub trait SessionDatabase: Clone + Debug + Send + Sync + 'static {
+    /// Returns true if Sessions are Persisted, Memory only backends return false so
+    /// Sessions are kept in Memory till they Expire.
+    fn is_persistent(&self) -> bool {
+        true
+    }
+
+    /// Creates the Session table if it does not already exist.
+    async fn migrate(&self, table_name: &str) -> Result<(), SessionError>;
+
+    /// Removes every Session that has expired.
+    async fn cleanup(&self, table_name: &str) -> Result<(), SessionError>;
+
+    /// Returns a Count of all Sessions currently within the Database.
+    async fn count(&self, table_name: &str) -> Result<i64, SessionError>;
+
+    /// Loads the serialized Session by its ID if it exists and has not expired.
+    async fn load(&self, id: &str, table_name: &str) -> Result<Option<Vec<u8>>, SessionError>;
+
+    /// Inserts or Updates the serialized Session under its ID.
+    async fn store(
+        &self,
+        id: &str,
+        session: &[u8],
+        expires: DateTime<Utc>,
+        table_name: &str,
+    ) -> Result<(), SessionError>;
+
+    /// Deletes a single Session by its ID.
+    async fn delete(&self, id: &str, table_name: &str) -> Result<(), SessionError>;
+
+    /// Deletes all Sessions from the Database.
+    async fn clear(&self, table_name: &str) -> Result<(), SessionError>;
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+pub(crate) fn substitute_table_name(query: &str, table_name: &str) -> String {
+    query.replace("%%TABLE_NAME%%", table_name)
+}
@@ -0,0 +1,13 @@
+mod database;
+#[cfg(feature = "mysql")]
+mod mysql;
+mod null;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(any(feature = "postgres", feature = "sqlite", feature = "mysql"))]
+pub(crate) use database::substitute_table_name;
+pub use database::SessionDatabase;
+pub use null::SessionNullPool;
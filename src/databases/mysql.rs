@@ -0,0 +1,128 @@
+use crate::{databases::substitute_table_name, SessionDatabase, SessionError};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::mysql::MySqlPool;
+
+///Mysql and MariaDB backend, uses DATETIME for expires since TIMESTAMP ends in 2038
+/// and LONGBLOB for the Session since BLOB is limited to 64KB.
+#[async_trait]
+impl SessionDatabase for MySqlPool {
+    async fn migrate(&self, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            r#"
+            CREATE TABLE IF NOT EXISTS %%TABLE_NAME%% (
+                `id` VARCHAR(128) NOT NULL PRIMARY KEY,
+                `expires` DATETIME NULL,
+                `session` LONGBLOB NOT NULL
+            )
+            "#,
+            table_name,
+        ))
+        .execute(self)
+        .await?;
+
+        // Tables created by older versions store the Session as TEXT or BLOB.
+        let data_type: Option<(String,)> = sqlx::query_as(
+            "SELECT DATA_TYPE FROM information_schema.columns WHERE table_schema = DATABASE() AND table_name = ? AND column_name = 'session'",
+        )
+        .bind(table_name)
+        .fetch_optional(self)
+        .await?;
+
+        if !matches!(data_type, Some((data_type,)) if data_type.eq_ignore_ascii_case("longblob")) {
+            sqlx::query(&substitute_table_name(
+                "ALTER TABLE %%TABLE_NAME%% MODIFY `session` LONGBLOB NOT NULL",
+                table_name,
+            ))
+            .execute(self)
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn cleanup(&self, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            "DELETE FROM %%TABLE_NAME%% WHERE expires < ?",
+            table_name,
+        ))
+        .bind(Utc::now())
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn count(&self, table_name: &str) -> Result<i64, SessionError> {
+        let (count,) = sqlx::query_as(&substitute_table_name(
+            "SELECT COUNT(*) FROM %%TABLE_NAME%%",
+            table_name,
+        ))
+        .fetch_one(self)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn load(&self, id: &str, table_name: &str) -> Result<Option<Vec<u8>>, SessionError> {
+        let result: Option<(Vec<u8>,)> = sqlx::query_as(&substitute_table_name(
+            "SELECT CAST(session AS BINARY) FROM %%TABLE_NAME%% WHERE id = ? AND (expires IS NULL OR expires > ?)",
+            table_name,
+        ))
+        .bind(id)
+        .bind(Utc::now())
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result.map(|(session,)| session))
+    }
+
+    async fn store(
+        &self,
+        id: &str,
+        session: &[u8],
+        expires: DateTime<Utc>,
+        table_name: &str,
+    ) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            r#"
+            INSERT INTO %%TABLE_NAME%%
+              (id, session, expires) VALUES (?, ?, ?)
+            ON DUPLICATE KEY UPDATE
+              expires = VALUES(expires),
+              session = VALUES(session)
+            "#,
+            table_name,
+        ))
+        .bind(id)
+        .bind(session)
+        .bind(expires)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            "DELETE FROM %%TABLE_NAME%% WHERE id = ?",
+            table_name,
+        ))
+        .bind(id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn clear(&self, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            "TRUNCATE %%TABLE_NAME%%",
+            table_name,
+        ))
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+}
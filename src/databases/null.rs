@@ -0,0 +1,49 @@
+use crate::{SessionDatabase, SessionError};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+///Placeholder Database for Sessions that only live in Memory, It never Persists anything.
+/// Use it with `SqlxSessionLayer::new_memory` when no Database feature is needed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SessionNullPool;
+
+#[async_trait]
+impl SessionDatabase for SessionNullPool {
+    fn is_persistent(&self) -> bool {
+        false
+    }
+
+    async fn migrate(&self, _table_name: &str) -> Result<(), SessionError> {
+        Ok(())
+    }
+
+    async fn cleanup(&self, _table_name: &str) -> Result<(), SessionError> {
+        Ok(())
+    }
+
+    async fn count(&self, _table_name: &str) -> Result<i64, SessionError> {
+        Ok(0)
+    }
+
+    async fn load(&self, _id: &str, _table_name: &str) -> Result<Option<Vec<u8>>, SessionError> {
+        Ok(None)
+    }
+
+    async fn store(
+        &self,
+        _id: &str,
+        _session: &[u8],
+        _expires: DateTime<Utc>,
+        _table_name: &str,
+    ) -> Result<(), SessionError> {
+        Ok(())
+    }
+
+    async fn delete(&self, _id: &str, _table_name: &str) -> Result<(), SessionError> {
+        Ok(())
+    }
+
+    async fn clear(&self, _table_name: &str) -> Result<(), SessionError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use crate::{databases::substitute_table_name, SessionDatabase, SessionError};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgPool;
+
+///Postgresql backend, this was the original Session Store and uses a BYTEA column for the Session.
+/// With the Json codec it can still be queried using `convert_from(session, 'UTF8')::JSONB`.
+#[async_trait]
+impl SessionDatabase for PgPool {
+    async fn migrate(&self, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            r#"
+            CREATE TABLE IF NOT EXISTS %%TABLE_NAME%% (
+                "id" VARCHAR NOT NULL PRIMARY KEY,
+                "expires" TIMESTAMP WITH TIME ZONE NULL,
+                "session" BYTEA NOT NULL
+            )
+            "#,
+            table_name,
+        ))
+        .execute(self)
+        .await?;
+
+        // Tables created by older versions store the Session as TEXT or JSONB. The column is
+        // looked up through regclass so it is the same table the ALTER resolves to.
+        sqlx::query(&substitute_table_name(
+            r#"
+            DO $$
+            BEGIN
+                IF EXISTS (
+                    SELECT 1 FROM pg_attribute
+                    WHERE attrelid = '%%TABLE_NAME%%'::regclass AND attname = 'session' AND NOT attisdropped
+                        AND atttypid IN ('text'::regtype, 'jsonb'::regtype)
+                ) THEN
+                    ALTER TABLE %%TABLE_NAME%% ALTER COLUMN "session" TYPE BYTEA USING convert_to("session"::TEXT, 'UTF8');
+                END IF;
+            END
+            $$
+            "#,
+            table_name,
+        ))
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn cleanup(&self, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            "DELETE FROM %%TABLE_NAME%% WHERE expires < $1",
+            table_name,
+        ))
+        .bind(Utc::now())
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn count(&self, table_name: &str) -> Result<i64, SessionError> {
+        let (count,) = sqlx::query_as(&substitute_table_name(
+            "SELECT COUNT(*) FROM %%TABLE_NAME%%",
+            table_name,
+        ))
+        .fetch_one(self)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn load(&self, id: &str, table_name: &str) -> Result<Option<Vec<u8>>, SessionError> {
+        let result: Option<(Vec<u8>,)> = sqlx::query_as(&substitute_table_name(
+            "SELECT session FROM %%TABLE_NAME%% WHERE id = $1 AND (expires IS NULL OR expires > $2)",
+            table_name,
+        ))
+        .bind(id)
+        .bind(Utc::now())
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result.map(|(session,)| session))
+    }
+
+    async fn store(
+        &self,
+        id: &str,
+        session: &[u8],
+        expires: DateTime<Utc>,
+        table_name: &str,
+    ) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            r#"
+            INSERT INTO %%TABLE_NAME%%
+              (id, session, expires) SELECT $1, $2, $3
+            ON CONFLICT(id) DO UPDATE SET
+              expires = EXCLUDED.expires,
+              session = EXCLUDED.session
+            "#,
+            table_name,
+        ))
+        .bind(id)
+        .bind(session)
+        .bind(expires)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            "DELETE FROM %%TABLE_NAME%% WHERE id = $1",
+            table_name,
+        ))
+        .bind(id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn clear(&self, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            "TRUNCATE %%TABLE_NAME%%",
+            table_name,
+        ))
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{databases::substitute_table_name, SessionDatabase, SessionError};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqlitePool;
+
+///Sqlite backend, expires is stored as a Unix timestamp since Sqlite lacks a Timezone aware type.
+#[async_trait]
+impl SessionDatabase for SqlitePool {
+    async fn migrate(&self, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            r#"
+            CREATE TABLE IF NOT EXISTS %%TABLE_NAME%% (
+                "id" TEXT NOT NULL PRIMARY KEY,
+                "expires" INTEGER NULL,
+                "session" BLOB NOT NULL
+            )
+            "#,
+            table_name,
+        ))
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn cleanup(&self, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            "DELETE FROM %%TABLE_NAME%% WHERE expires < ?",
+            table_name,
+        ))
+        .bind(Utc::now().timestamp())
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn count(&self, table_name: &str) -> Result<i64, SessionError> {
+        let (count,) = sqlx::query_as(&substitute_table_name(
+            "SELECT COUNT(*) FROM %%TABLE_NAME%%",
+            table_name,
+        ))
+        .fetch_one(self)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn load(&self, id: &str, table_name: &str) -> Result<Option<Vec<u8>>, SessionError> {
+        let result: Option<(Vec<u8>,)> = sqlx::query_as(&substitute_table_name(
+            "SELECT CAST(session AS BLOB) FROM %%TABLE_NAME%% WHERE id = ? AND (expires IS NULL OR expires > ?)",
+            table_name,
+        ))
+        .bind(id)
+        .bind(Utc::now().timestamp())
+        .fetch_optional(self)
+        .await?;
+
+        Ok(result.map(|(session,)| session))
+    }
+
+    async fn store(
+        &self,
+        id: &str,
+        session: &[u8],
+        expires: DateTime<Utc>,
+        table_name: &str,
+    ) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            r#"
+            INSERT INTO %%TABLE_NAME%%
+              (id, session, expires) VALUES (?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+              expires = excluded.expires,
+              session = excluded.session
+            "#,
+            table_name,
+        ))
+        .bind(id)
+        .bind(session)
+        .bind(expires.timestamp())
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            "DELETE FROM %%TABLE_NAME%% WHERE id = ?",
+            table_name,
+        ))
+        .bind(id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn clear(&self, table_name: &str) -> Result<(), SessionError> {
+        sqlx::query(&substitute_table_name(
+            "DELETE FROM %%TABLE_NAME%%",
+            table_name,
+        ))
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+}
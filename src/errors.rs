@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+///Errors that can be returned from the Session Store or any of its Database backends.
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error(transparent)]
+    Sqlx(#[from] sqlx::Error),
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+    #[cfg(feature = "msgpack")]
+    #[error(transparent)]
+    MessagePackEncode(#[from] rmp_serde::encode::Error),
+    #[cfg(feature = "msgpack")]
+    #[error(transparent)]
+    MessagePackDecode(#[from] rmp_serde::decode::Error),
+    #[cfg(feature = "cbor")]
+    #[error(transparent)]
+    CborEncode(#[from] ciborium::ser::Error<std::io::Error>),
+    #[cfg(feature = "cbor")]
+    #[error(transparent)]
+    CborDecode(#[from] ciborium::de::Error<std::io::Error>),
+    #[cfg(feature = "bincode")]
+    #[error(transparent)]
+    Bincode(#[from] bincode::Error),
+}
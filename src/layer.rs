@@ -1,23 +1,48 @@
-use crate::{SQLxSessionManager, SQLxSessionStore, SqlxSessionConfig};
-use sqlx::postgres::PgPool;
+use crate::{
+    SQLxSessionManager, SQLxSessionStore, SQLxSessionSweeper, SessionDatabase, SessionNullPool,
+    SqlxSessionConfig,
+};
 use tower_layer::Layer;
 
 /// Session layer struct used for starting the Manager when a user comes on board.
 #[derive(Clone, Debug)]
-pub struct SqlxSessionLayer {
-    store: SQLxSessionStore,
+pub struct SqlxSessionLayer<T>
+where
+    T: SessionDatabase,
+{
+    store: SQLxSessionStore<T>,
 }
 
-impl SqlxSessionLayer {
+impl<T> SqlxSessionLayer<T>
+where
+    T: SessionDatabase,
+{
     /// Creates the SQLx Session Layer.
-    pub fn new(config: SqlxSessionConfig, poll: PgPool) -> Self {
+    pub fn new(config: SqlxSessionConfig, poll: T) -> Self {
         let store = SQLxSessionStore::new(poll, config);
         Self { store }
     }
+
+    /// Spawns the background Session sweeper if a sweep interval was set in the config.
+    /// Keep the returned handle so the sweeper can be stopped on shutdown.
+    pub fn spawn_sweeper(&self) -> Option<SQLxSessionSweeper> {
+        self.store.spawn_sweeper()
+    }
+}
+
+impl SqlxSessionLayer<SessionNullPool> {
+    /// Creates the SQLx Session Layer without a Database.
+    /// Sessions will only live in Memory and are lost once they expire or the server restarts.
+    pub fn new_memory(config: SqlxSessionConfig) -> Self {
+        Self::new(config, SessionNullPool)
+    }
 }
 
-impl<S> Layer<S> for SqlxSessionLayer {
-    type Service = SQLxSessionManager<S>;
+impl<S, T> Layer<S> for SqlxSessionLayer<T>
+where
+    T: SessionDatabase,
+{
+    type Service = SQLxSessionManager<S, T>;
 
     ///This is called as soon as the session layer is placed within .layer of axum.
     fn layer(&self, service: S) -> Self::Service {
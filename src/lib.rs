@@ -1,20 +1,29 @@
 #![doc = include_str!("../README.md")]
-//Todo: Support more databases and expand the Tokio/RLS or RustRLS Selections for SQLx
-///This Library Requires that Tower_Cookies is used as an active layer.
+//Todo: Expand the Tokio/RLS or RustRLS Selections for SQLx
+mod codec;
 mod config;
-mod future;
+mod databases;
+mod errors;
 mod layer;
 mod manager;
 mod session;
 mod session_data;
 mod session_id;
 mod session_store;
+mod sweeper;
+mod transport;
+mod typed_session;
 
-pub use config::SqlxSessionConfig;
-pub use future::ResponseFuture;
+pub use codec::SQLxSessionCodec;
+pub use config::{SQLxSessionTransport, SqlxSessionConfig};
+pub use cookie::{Key, SameSite};
+pub use databases::{SessionDatabase, SessionNullPool};
+pub use errors::SessionError;
 pub use layer::SqlxSessionLayer;
 pub use manager::SQLxSessionManager;
 pub use session::SQLxSession;
 pub use session_data::SQLxSessionData;
 pub use session_id::SQLxSessionID;
 pub use session_store::SQLxSessionStore;
+pub use sweeper::SQLxSessionSweeper;
+pub use typed_session::{SQLxSessionKey, SQLxTypedSession};
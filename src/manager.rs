@@ -1,38 +1,49 @@
-use crate::future::ResponseFuture;
-use crate::{SQLxSession, SQLxSessionData, SQLxSessionID, SQLxSessionStore};
-use chrono::{Duration, Utc};
-use futures::executor::block_on;
+use crate::{
+    session_data::add_lifespan, transport::Transport, SQLxSession, SQLxSessionData, SQLxSessionID,
+    SQLxSessionStore, SessionDatabase, SqlxSessionConfig,
+};
+use chrono::Utc;
+use futures::future::BoxFuture;
 use http::{Request, Response};
-use parking_lot::{Mutex, RwLockUpgradableReadGuard};
+use parking_lot::Mutex;
 use std::collections::HashMap;
+use std::sync::atomic::Ordering;
 use std::task::{Context, Poll};
-use tower_cookies::{Cookie, Cookies};
 use tower_service::Service;
-use uuid::Uuid;
 
 ///This manages the other services that can be seen in inner and gives access to the store.
 /// the store is cloneable hence per each SQLxSession we clone it as we use thread Read write locks
 /// to control any data that needs to be accessed across threads that cant be cloned.
 #[derive(Clone, Debug)]
-pub struct SQLxSessionManager<S> {
+pub struct SQLxSessionManager<S, T>
+where
+    T: SessionDatabase,
+{
     inner: S,
-    store: SQLxSessionStore,
+    store: SQLxSessionStore<T>,
 }
 
-impl<S> SQLxSessionManager<S> {
+impl<S, T> SQLxSessionManager<S, T>
+where
+    T: SessionDatabase,
+{
     /// Create a new cookie manager.
-    pub fn new(inner: S, store: SQLxSessionStore) -> Self {
+    pub fn new(inner: S, store: SQLxSessionStore<T>) -> Self {
         Self { inner, store }
     }
 }
 
-impl<ReqBody, ResBody, S> Service<Request<ReqBody>> for SQLxSessionManager<S>
+impl<ReqBody, ResBody, S, T> Service<Request<ReqBody>> for SQLxSessionManager<S, T>
 where
-    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+    ResBody: Send + 'static,
+    T: SessionDatabase,
 {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = ResponseFuture<S::Future>;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
 
     ///lets the system know it is ready for the next step
     #[inline]
@@ -40,137 +51,325 @@ where
         self.inner.poll_ready(cx)
     }
 
-    /// Is called on Request to generate any needed data and sets a future to be used on the Response
+    /// Is called on Request to generate any needed data and returns a future that loads the
+    /// Session, runs the inner Service and then Saves the Session once the Response is ready.
     /// This is where we will Generate the SQLxSession for the end user and where we add the Cookies.
-    //TODO: Make lifespan Adjustable to be Permenant, Per Session OR Based on a Set Duration from Config.
     fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
         let store = self.store.clone();
-        // We Extract the Tower_Cookies Extensions Variable so we can add Cookies to it. Some reason can only be done here..?
-        let cookies = req
-            .extensions()
-            .get::<Cookies>()
-            .expect("`Tower_Cookie` extension missing");
-
-        let session = SQLxSession {
-            id: {
-                let store_ug = store.inner.upgradable_read();
-
-                let id = if let Some(cookie) = cookies.get(&store.config.cookie_name) {
-                    (
-                        SQLxSessionID(
-                            Uuid::parse_str(cookie.value()).expect("`Could not parse Uuid"),
-                        ),
-                        false,
-                    )
-                } else {
-                    let new_id = loop {
-                        let token = Uuid::new_v4();
-
-                        if !store_ug.contains_key(&token.to_string()) {
-                            break token;
-                        }
-                    };
-
-                    (SQLxSessionID(new_id), true)
-                };
-
-                if id.1 == false {
-                    if let Some(m) = store_ug.get(&id.0.to_string()) {
-                        let mut inner = m.lock();
-
-                        if inner.expires < Utc::now() || inner.destroy {
-                            // Database Session expired, reuse the ID but drop data.
-                            inner.data = HashMap::new();
-                        }
-
-                        // Session is extended by making a request with valid ID
-                        inner.expires = Utc::now() + store.config.lifespan;
-                        inner.autoremove = Utc::now() + store.config.memory_lifespan;
-                    } else {
-                        let mut store_wg = RwLockUpgradableReadGuard::upgrade(store_ug);
-
-                        let mut sess = block_on(store.load_session(id.0.to_string()))
-                            .ok()
-                            .flatten()
-                            .unwrap_or(SQLxSessionData {
-                                id: id.0 .0.clone(),
-                                data: HashMap::new(),
-                                expires: Utc::now() + Duration::hours(6),
-                                destroy: false,
-                                autoremove: Utc::now() + store.config.memory_lifespan,
-                            });
-
-                        if !sess.validate() || sess.destroy {
-                            sess.data = HashMap::new();
-                            sess.expires = Utc::now() + Duration::hours(6);
-                            sess.autoremove = Utc::now() + store.config.memory_lifespan;
-                        }
-
-                        let mut cookie =
-                            Cookie::new(store.config.cookie_name.clone(), id.0 .0.to_string());
-
-                        cookie.make_permanent();
-
-                        cookies.add(cookie);
-                        store_wg.insert(id.0 .0.to_string(), Mutex::new(sess));
-                    }
-                } else {
-                    // --- New ID was generated Lets make a session for it ---
-                    // Get exclusive write access to the map
-                    let mut store_wg = RwLockUpgradableReadGuard::upgrade(store_ug);
-
-                    // This branch runs less often, and we already have write access,
-                    // let's check if any sessions expired. We don't want to hog memory
-                    // forever by abandoned sessions (e.g. when a client lost their cookie)
-                    {
-                        let timers = store.timers.upgradable_read();
-                        // Throttle by memory lifespan - e.g. sweep every hour
-                        if timers.last_expiry_sweep <= Utc::now() {
-                            let mut timers = RwLockUpgradableReadGuard::upgrade(timers);
-                            store_wg.retain(|_k, v| v.lock().autoremove > Utc::now());
-                            timers.last_expiry_sweep = Utc::now() + store.config.memory_lifespan;
-                        }
+        // The inner service was polled ready, so we swap in a clone and move the ready one
+        // into the future.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let mut transport = Transport::new(&store.config, &req);
+
+            // Malformed, timed out or unknown IDs are never reused so a new Session replaces them.
+            let id = match transport.get() {
+                Some(Some(id)) if load_session(&store, &mut transport, &id).await => id,
+                Some(sent) => {
+                    // Any client can send these so they are only logged at debug.
+                    if sent.is_none() {
+                        tracing::debug!("Invalid Session cookie, creating a new Session");
                     }
 
-                    {
-                        let timers = store.timers.upgradable_read();
-                        // Throttle by database lifespan - e.g. sweep every 6 hours
-                        if timers.last_database_expiry_sweep <= Utc::now() {
-                            let mut timers = RwLockUpgradableReadGuard::upgrade(timers);
-                            store_wg.retain(|_k, v| v.lock().autoremove > Utc::now());
-                            let _ = block_on(store.cleanup());
-                            timers.last_database_expiry_sweep = Utc::now() + store.config.lifespan;
-                        }
+                    // Lazy Sessions get no cookie till data is set so the old one is expired now.
+                    if store.config.lazy_sessions {
+                        transport.remove();
                     }
 
-                    let mut cookie =
-                        Cookie::new(store.config.cookie_name.clone(), id.0 .0.to_string());
-                    cookie.make_permanent();
-                    cookies.add(cookie);
+                    create_session(&store, &mut transport).await
+                }
+                None => create_session(&store, &mut transport).await,
+            };
 
-                    let sess = SQLxSessionData {
-                        id: id.0 .0.clone(),
-                        data: HashMap::new(),
-                        expires: Utc::now() + Duration::hours(6),
-                        destroy: false,
-                        autoremove: Utc::now() + store.config.memory_lifespan,
-                    };
+            let session = SQLxSession {
+                id: id.clone(),
+                store: store.clone(),
+            };
 
-                    store_wg.insert(id.0 .0.to_string(), Mutex::new(sess));
-                }
+            //Sets a clone of the Store in the Extensions for Direct usage and sets the Session for Direct usage
+            req.extensions_mut().insert(store.clone());
+            req.extensions_mut().insert(session.clone());
+
+            let mut response = inner.call(req).await?;
+
+            // Destroyed Sessions have no cookie to set so Max-Age is irrelevant.
+            let max_age = save_session(&store, &mut transport, id)
+                .await
+                .and_then(|id| {
+                    store
+                        .inner
+                        .read()
+                        .get(&id.0)
+                        .and_then(|sess| sess.lock().cookie_max_age())
+                });
+
+            transport.apply(&mut response, max_age);
+
+            Ok(response)
+        })
+    }
+}
+
+/// Runs once the Response is ready. Destroyed Sessions are removed from Memory and the Database
+/// right away and their cookie expired, Renewed Sessions are moved to a new ID and then
+/// the Session is Saved if it changed. Returns the Sessions final ID unless it was Destroyed.
+async fn save_session<T>(
+    store: &SQLxSessionStore<T>,
+    transport: &mut Transport<'_>,
+    id: SQLxSessionID,
+) -> Option<SQLxSessionID>
+where
+    T: SessionDatabase,
+{
+    let (destroy, renew, reissue, provisional) = store
+        .inner
+        .read()
+        .get(&id.0)
+        .map(|sess| {
+            let mut sess = sess.lock();
+            // Provisional Sessions become real ones once data was set and need their cookie.
+            let provisional = sess.provisional && sess.data.is_empty();
+            let reissue = sess.reissue || (sess.provisional && !provisional);
+            sess.reissue = false;
+            sess.provisional = provisional;
+            (sess.destroy, sess.renew, reissue, provisional)
+        })
+        .unwrap_or((false, false, false, false));
+
+    // Lazy Sessions that never had data set are dropped without a cookie or Database row.
+    if provisional {
+        store.inner.write().remove(&id.0);
+        transport.cancel();
+        return None;
+    }
+
+    if destroy {
+        store.inner.write().remove(&id.0);
+        transport.remove();
+
+        if let Err(err) = store.destroy_session(&id.0).await {
+            tracing::error!("Failed to destroy Session: {}", err);
+        }
+
+        return None;
+    }
+
+    let id = if renew {
+        renew_session(store, transport, id).await
+    } else {
+        if reissue {
+            transport.issue(&id);
+        }
+
+        id
+    };
+
+    if !store.is_persistent() {
+        return Some(id);
+    }
+
+    //Clone the Session if it changed so it can be Saved to the Database without holding any locks.
+    let session_data = store.inner.read().get(&id.0).and_then(|sess| {
+        let mut sess = sess.lock();
+
+        if sess.update {
+            sess.update = false;
+            Some(sess.clone())
+        } else {
+            None
+        }
+    });
 
-                id.0
-            },
-            store: store.clone(),
+    if let Some(session_data) = session_data {
+        if let Err(err) = store.store_session(session_data).await {
+            tracing::error!("Failed to save Session: {}", err);
+            // Try again on the next request.
+            if let Some(sess) = store.inner.read().get(&id.0) {
+                sess.lock().update = true;
+            }
+        }
+    }
+
+    Some(id)
+}
+
+/// Moves the Session under a newly generated ID so the old one can no longer be used.
+/// The old ID is removed from the Database and the new ID is issued to the client.
+async fn renew_session<T>(
+    store: &SQLxSessionStore<T>,
+    transport: &mut Transport<'_>,
+    old_id: SQLxSessionID,
+) -> SQLxSessionID
+where
+    T: SessionDatabase,
+{
+    let id = {
+        let mut store_wg = store.inner.write();
+
+        let sess = match store_wg.remove(&old_id.0) {
+            Some(sess) => sess,
+            None => return old_id,
         };
 
-        //Sets a clone of the Store in the Extensions for Direct usage and sets the Session for Direct usage
-        req.extensions_mut().insert(self.store.clone());
-        req.extensions_mut().insert(session.clone());
+        let id = generate_id(&store_wg, &store.config);
+
+        {
+            let mut inner = sess.lock();
+            inner.id = id.inner();
+            inner.renew = false;
+            inner.update = true;
+        }
+
+        store_wg.insert(id.inner(), sess);
+        id
+    };
+
+    if let Err(err) = store.destroy_session(&old_id.0).await {
+        tracing::error!("Failed to destroy renewed Session: {}", err);
+    }
+
+    transport.issue(&id);
+    id
+}
+
+/// Extends the Session if it is already within Memory otherwise it is loaded from the Database.
+/// No locks are held while waiting on the Database. Returns false if the Session timed out or
+/// does not exist, it is then removed so its ID can not be used again.
+async fn load_session<T>(
+    store: &SQLxSessionStore<T>,
+    transport: &mut Transport<'_>,
+    id: &SQLxSessionID,
+) -> bool
+where
+    T: SessionDatabase,
+{
+    let in_memory = store.inner.read().get(&id.0).map(|m| {
+        let mut inner = m.lock();
+
+        if !inner.validate(&store.config) || inner.destroy {
+            return false;
+        }
+
+        // Session is extended by making a request with valid ID, a persistent cookie's
+        // Max-Age is refreshed along with it.
+        if inner.extend(&store.config) {
+            transport.issue(id);
+        }
+
+        true
+    });
+
+    let sess = match in_memory {
+        Some(true) => return true,
+        Some(false) => None,
+        None => match store.load_session(id.inner()).await {
+            Ok(sess) => sess.filter(|sess| sess.validate(&store.config) && !sess.destroy),
+            Err(err) => {
+                tracing::error!("Failed to load Session: {}", err);
+                return false;
+            }
+        },
+    };
+
+    let mut sess = match sess {
+        Some(sess) => sess,
+        None => {
+            // Expired rows are not returned by the Database so remove them along with Memory.
+            store.inner.write().remove(&id.0);
+
+            if let Err(err) = store.destroy_session(&id.0).await {
+                tracing::error!("Failed to destroy timed out Session: {}", err);
+            }
+
+            return false;
+        }
+    };
+
+    sess.extend(&store.config);
+
+    transport.issue(id);
+
+    // Another request may have loaded it while we waited on the Database so keep theirs.
+    store
+        .inner
+        .write()
+        .entry(id.inner())
+        .or_insert_with(|| Mutex::new(sess));
+
+    true
+}
+
+/// Generates a new unused ID and creates a Session for it. Since this branch runs less often
+/// it is also where we sweep expired Sessions from Memory and the Database, unless the
+/// background sweeper handles it.
+async fn create_session<T>(
+    store: &SQLxSessionStore<T>,
+    transport: &mut Transport<'_>,
+) -> SQLxSessionID
+where
+    T: SessionDatabase,
+{
+    let (sweep_memory, sweep_database) = if store.sweeper_active.load(Ordering::SeqCst) {
+        (false, false)
+    } else {
+        let mut timers = store.timers.write();
+        let now = Utc::now();
+        // Throttle by memory lifespan - e.g. sweep every hour
+        let sweep_memory = timers.last_expiry_sweep <= now;
+        // Throttle by database lifespan - e.g. sweep every 6 hours
+        let sweep_database = timers.last_database_expiry_sweep <= now;
+
+        if sweep_memory {
+            timers.last_expiry_sweep = add_lifespan(now, store.config.memory_lifespan);
+        }
+
+        if sweep_database {
+            timers.last_database_expiry_sweep = add_lifespan(now, store.config.lifespan);
+        }
+
+        (sweep_memory, sweep_database)
+    };
+
+    if sweep_database {
+        if let Err(err) = store.cleanup().await {
+            tracing::error!("Failed to cleanup Sessions: {}", err);
+        }
+    }
+
+    let mut store_wg = store.inner.write();
+
+    // We don't want to hog memory forever by abandoned sessions
+    // (e.g. when a client lost their cookie)
+    if sweep_memory || sweep_database {
+        store_wg.retain(|_k, v| store.retain_in_memory(&v.lock()));
+    }
+
+    let id = generate_id(&store_wg, &store.config);
+
+    let mut sess = SQLxSessionData::new(id.inner(), &store.config);
+    sess.provisional = store.config.lazy_sessions;
+    store_wg.insert(id.inner(), Mutex::new(sess));
+
+    // Lazy Sessions get their cookie once data is set within them.
+    if !store.config.lazy_sessions {
+        transport.issue(&id);
+    }
+
+    id
+}
+
+/// Generates a new ID that is not already in use within Memory.
+fn generate_id(
+    sessions: &HashMap<String, Mutex<SQLxSessionData>>,
+    config: &SqlxSessionConfig,
+) -> SQLxSessionID {
+    loop {
+        let id = SQLxSessionID::generate(config.cookie_len);
 
-        ResponseFuture {
-            future: self.inner.call(req),
-            session,
+        if !sessions.contains_key(&id.0) {
+            break id;
         }
     }
 }
@@ -1,27 +1,32 @@
-use crate::{SQLxSessionData, SQLxSessionID, SQLxSessionStore};
+use crate::{SQLxSessionData, SQLxSessionID, SQLxSessionStore, SessionDatabase};
 use axum::{
     async_trait,
     extract::{FromRequest, RequestParts},
     http::{self, StatusCode},
 };
-use futures::executor::block_on;
+use chrono::{DateTime, Duration, Utc};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use serde_json::Value;
 
 ///This is the Session that is generated when a user is routed to a page that Needs one
 /// It is used to Save and load session data similar to how it is done on python.
 #[derive(Debug, Clone)]
-pub struct SQLxSession {
-    pub(crate) store: SQLxSessionStore,
+pub struct SQLxSession<T>
+where
+    T: SessionDatabase,
+{
+    pub(crate) store: SQLxSessionStore<T>,
     pub(crate) id: SQLxSessionID,
 }
 
 /// this auto pulls a SQLxSession from the extensions when added by the Session managers call
 /// if for some reason the Session Manager did not run this will Error.
 #[async_trait]
-impl<B> FromRequest<B> for SQLxSession
+impl<B, T> FromRequest<B> for SQLxSession<T>
 where
     B: Send,
+    T: SessionDatabase,
 {
     type Rejection = (http::StatusCode, &'static str);
 
@@ -30,52 +35,110 @@ where
             StatusCode::INTERNAL_SERVER_ERROR,
             "Can't extract SQLxSession: extensions has been taken by another extractor",
         ))?;
-        extensions.get::<SQLxSession>().cloned().ok_or((
+        extensions.get::<SQLxSession<T>>().cloned().ok_or((
             StatusCode::INTERNAL_SERVER_ERROR,
             "Can't extract SQLxSession. Is `SQLxSessionLayer` enabled?",
         ))
     }
 }
 
-impl SQLxSession {
+impl<T> SQLxSession<T>
+where
+    T: SessionDatabase,
+{
     ///Runs a Closure that can return Data from the users SessionData Hashmap.
-    pub fn tap<T: DeserializeOwned>(
-        &self,
-        func: impl FnOnce(&mut SQLxSessionData) -> Option<T>,
-    ) -> Option<T> {
-        let store_rg = self.store.inner.read();
+    pub fn tap<N>(&self, func: impl FnOnce(&SQLxSessionData) -> Option<N>) -> Option<N> {
+        self.modify(|sess| func(sess))
+    }
+
+    ///Runs a Closure that can change the users SessionData, the Session is then marked to be Saved.
+    pub fn tap_mut<N>(&self, func: impl FnOnce(&mut SQLxSessionData) -> Option<N>) -> Option<N> {
+        self.modify(|sess| {
+            sess.update = true;
+            func(sess)
+        })
+    }
 
-        let mut instance = store_rg
-            .get(&self.id.0.to_string())
-            .expect("Session data unexpectedly missing")
-            .lock();
+    /// Runs a Closure on the users SessionData without marking it to be Saved,
+    /// callers set update themselves when something changed. Returns None if the Session
+    /// is no longer in Memory, e.g. when used after the Response was sent.
+    fn modify<N>(&self, func: impl FnOnce(&mut SQLxSessionData) -> Option<N>) -> Option<N> {
+        let store_rg = self.store.inner.read();
+        let mut instance = store_rg.get(&self.id.0)?.lock();
 
         func(&mut instance)
     }
 
-    ///Sets the Entire Session to be Cleaned on next load.
+    ///Sets the Entire Session to be Destroyed once the Response is ready.
+    /// It is removed from Memory and the Database and its cookie is expired.
     pub fn destroy(&self) {
-        self.tap(|sess| {
+        self.modify(|sess| {
             sess.destroy = true;
+            sess.update = true;
+            Some(1)
+        });
+    }
+
+    ///Moves the Session to a newly generated ID once the Response is ready and removes the old one.
+    /// Use this on login or privilege changes to prevent session fixation.
+    pub fn renew(&self) {
+        self.modify(|sess| {
+            sess.renew = true;
+            sess.update = true;
+            Some(1)
+        });
+    }
+
+    ///Sets if the Session is Persistent, e.g. for "remember me" logins. Persistent Sessions use
+    /// the persistent lifespan and their cookie outlives the browser, otherwise the cookie
+    /// is removed once the browser is closed.
+    pub fn set_persistent(&self, persistent: bool) {
+        self.modify(|sess| {
+            if sess.persistent != persistent {
+                sess.persistent = persistent;
+                sess.reset_expiry(&self.store.config);
+            }
+            Some(1)
+        });
+    }
+
+    ///Sets a custom lifespan for this Session only, e.g. shorter lived admin Sessions.
+    /// It overrides the config lifespans and the Session keeps being extended by it on each request.
+    pub fn set_expiry(&self, lifespan: Duration) {
+        self.modify(|sess| {
+            sess.custom_lifespan = Some(lifespan.num_seconds());
+            sess.fixed_expiry = false;
+            sess.reset_expiry(&self.store.config);
+            Some(1)
+        });
+    }
+
+    ///Sets when this Session Expires. Requests no longer extend it past this point.
+    pub fn set_expires_at(&self, expires: DateTime<Utc>) {
+        self.modify(|sess| {
+            sess.expires = expires;
+            sess.fixed_expiry = true;
+            sess.reset_expiry(&self.store.config);
             Some(1)
         });
     }
 
     ///Used to get data stored within SessionDatas hashmap from a key value.
-    pub fn get<T: serde::de::DeserializeOwned>(&self, key: &str) -> Option<T> {
+    pub fn get<N: DeserializeOwned>(&self, key: &str) -> Option<N> {
         self.tap(|sess| {
-            let string = sess.data.get(key)?;
-            serde_json::from_str(string).ok()
+            let value = sess.data.get(key)?;
+            serde_json::from_value(value.clone()).ok()
         })
     }
 
     /// Used to Set data to SessionData via a Key and the Value to Set.
     pub fn set(&self, key: &str, value: impl Serialize) {
-        let value = serde_json::to_string(&value).unwrap_or_else(|_| "".to_string());
+        let value = serde_json::to_value(&value).unwrap_or(Value::Null);
 
-        self.tap(|sess| {
+        self.modify(|sess| {
             if sess.data.get(key) != Some(&value) {
                 sess.data.insert(key.to_string(), value);
+                sess.update = true;
             }
             Some(1)
         });
@@ -83,20 +146,29 @@ impl SQLxSession {
 
     ///used to remove a key and its data from SessionData's Hashmap
     pub fn remove(&self, key: &str) {
-        self.tap(|sess| sess.data.remove(key));
+        self.modify(|sess| {
+            let removed = sess.data.remove(key)?;
+            sess.update = true;
+            Some(removed)
+        });
     }
 
-    /// Will instantly clear all data from SessionData's Hashmap
-    pub fn clear_all(&self) {
-        self.tap(|sess| {
+    /// Will instantly clear all data from SessionData's Hashmap and clear the Database.
+    /// No locks are held while waiting on the Database.
+    pub async fn clear_all(&self) {
+        self.modify(|sess| {
             sess.data.clear();
-            let _ = block_on(self.store.clear_store());
+            sess.update = true;
             Some(1)
         });
+
+        if let Err(err) = self.store.clear_store().await {
+            tracing::error!("Failed to clear Sessions: {}", err);
+        }
     }
 
     /// Returns a Count of all Sessions currently within the Session Store.
-    pub fn count(&self) -> i64 {
-        block_on(self.store.count()).unwrap_or(0i64)
+    pub async fn count(&self) -> i64 {
+        self.store.count().await.unwrap_or(0i64)
     }
 }
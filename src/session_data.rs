@@ -1,20 +1,284 @@
-use chrono::{DateTime, Utc};
+use crate::SqlxSessionConfig;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
-use uuid::Uuid;
+
+/// Current format of Saved Sessions. Format 0 Sessions were Saved by older versions
+/// that kept each value as a JSON string.
+pub(crate) const SESSION_FORMAT: u32 = 1;
+
+/// Adds a lifespan to a time. Lifespans past the year 9999 are capped to its end since
+/// the Databases can not store later dates, e.g. `Duration::MAX` for Sessions that never Expire.
+pub(crate) fn add_lifespan(time: DateTime<Utc>, lifespan: Duration) -> DateTime<Utc> {
+    let max = NaiveDate::from_ymd_opt(9999, 12, 31)
+        .and_then(|date| date.and_hms_opt(23, 59, 59))
+        .map(|max| Utc.from_utc_datetime(&max))
+        .expect("valid max expiry");
+
+    time.checked_add_signed(lifespan)
+        .map_or(max, |time| std::cmp::min(time, max))
+}
 
 ///This Contains all of out Sessions Data including their Hashed Data they access.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SQLxSessionData {
-    pub id: Uuid,
-    pub data: HashMap<String, String>,
+    /// Not Saved to the Database, it is set from the requests ID when loaded.
+    #[serde(skip)]
+    pub id: String,
+    pub data: HashMap<String, Value>,
     pub expires: DateTime<Utc>,
     pub autoremove: DateTime<Utc>,
     pub destroy: bool,
+    /// When the Session was created, used for the absolute lifespan. Sessions Saved before it
+    /// existed start from when they are first loaded and are Saved again along with it.
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// Persistent Sessions use the longer persistent lifespan and a cookie that outlives
+    /// the browser, otherwise the cookie is removed once the browser closes.
+    #[serde(default)]
+    pub persistent: bool,
+    /// Custom lifespan in seconds that overrides the config lifespans for this Session.
+    #[serde(default)]
+    pub custom_lifespan: Option<i64>,
+    /// Set when expires was given directly, the Session is then no longer extended by requests.
+    #[serde(default)]
+    pub fixed_expiry: bool,
+    /// Format the Session was Saved in, missing on Sessions Saved by older versions.
+    #[serde(default)]
+    pub format: u32,
+    /// Set when the Session has changed and needs to be Saved to the Database.
+    #[serde(skip)]
+    pub update: bool,
+    /// Set when the Session should be moved to a new ID once the Response is ready.
+    #[serde(skip)]
+    pub renew: bool,
+    /// Set when the cookie needs to be issued again, e.g. after its lifespan changed.
+    #[serde(skip)]
+    pub reissue: bool,
+    /// Set on new Sessions in lazy mode. They are only Saved and given a cookie once data is set.
+    #[serde(skip)]
+    pub provisional: bool,
 }
 
 impl SQLxSessionData {
-    pub fn validate(&self) -> bool {
-        self.expires >= Utc::now()
+    /// Creates a new empty Session using the lifespans from the config.
+    pub(crate) fn new(id: String, config: &SqlxSessionConfig) -> Self {
+        let now = Utc::now();
+        let mut expires = add_lifespan(now, config.lifespan);
+
+        if let Some(absolute) = config.absolute_lifespan {
+            expires = std::cmp::min(expires, add_lifespan(now, absolute));
+        }
+
+        Self {
+            id,
+            data: HashMap::new(),
+            expires,
+            autoremove: add_lifespan(now, config.memory_lifespan),
+            destroy: false,
+            created_at: now,
+            persistent: false,
+            custom_lifespan: None,
+            fixed_expiry: false,
+            format: SESSION_FORMAT,
+            update: true,
+            renew: false,
+            reissue: false,
+            provisional: false,
+        }
+    }
+
+    /// Extends the Session by its lifespan. The new expiry is only kept and marked
+    /// for Saving once it has moved by at least the expiry refresh or half the lifespan,
+    /// otherwise every request would cause a Database write. Expiries past the absolute lifespan
+    /// are always moved back to it. Returns true if the expiry was moved.
+    pub(crate) fn extend(&mut self, config: &SqlxSessionConfig) -> bool {
+        let expires = self.clamp_expiry(add_lifespan(Utc::now(), self.lifespan(config)), config);
+        self.autoremove = add_lifespan(Utc::now(), config.memory_lifespan);
+
+        // Fixed expiries or ones Saved before the absolute lifespan was lowered are moved back to it.
+        let limit = self.clamp_expiry(self.expires, config);
+        let clamped = limit < self.expires;
+
+        if clamped {
+            self.expires = limit;
+            self.update = true;
+        }
+
+        if self.fixed_expiry {
+            return clamped;
+        }
+
+        // Short lived Sessions would otherwise never reach the refresh and expire while in use.
+        let refresh = std::cmp::min(config.expiry_refresh, self.lifespan(config) / 2);
+
+        if expires - self.expires >= refresh {
+            self.expires = expires;
+            self.update = true;
+            return true;
+        }
+
+        clamped
+    }
+
+    /// Restarts the Session's expiry from now using its lifespan unless the expiry is fixed.
+    /// The cookie is issued again so its Max-Age matches.
+    pub(crate) fn reset_expiry(&mut self, config: &SqlxSessionConfig) {
+        if !self.fixed_expiry {
+            self.expires = add_lifespan(Utc::now(), self.lifespan(config));
+        }
+
+        self.expires = self.clamp_expiry(self.expires, config);
+        self.update = true;
+        self.reissue = true;
+    }
+
+    /// Limits an expiry to the absolute lifespan so the Session Expires regardless of activity.
+    fn clamp_expiry(&self, expires: DateTime<Utc>, config: &SqlxSessionConfig) -> DateTime<Utc> {
+        match config.absolute_lifespan {
+            Some(absolute) => std::cmp::min(expires, add_lifespan(self.created_at, absolute)),
+            None => expires,
+        }
+    }
+
+    /// Returns how long the Session lives for after each request.
+    pub(crate) fn lifespan(&self, config: &SqlxSessionConfig) -> Duration {
+        if let Some(lifespan) = self.custom_lifespan {
+            Duration::seconds(lifespan)
+        } else if self.persistent {
+            config.persistent_lifespan
+        } else {
+            config.lifespan
+        }
+    }
+
+    /// Returns the Max-Age of the Session cookie, None makes it a browser session cookie.
+    /// The cookie Expires along with the Session.
+    pub(crate) fn cookie_max_age(&self) -> Option<Duration> {
+        if self.fixed_expiry || self.persistent || self.custom_lifespan.is_some() {
+            Some(std::cmp::max(self.expires - Utc::now(), Duration::zero()))
+        } else {
+            None
+        }
+    }
+
+    /// Converts a Session Saved by an older version to the current format.
+    /// Its values were kept as JSON strings so they are parsed once here.
+    pub(crate) fn upgrade(&mut self) {
+        if self.format >= SESSION_FORMAT {
+            return;
+        }
+
+        for value in self.data.values_mut() {
+            if let Value::String(string) = value {
+                if let Ok(parsed) = serde_json::from_str(string) {
+                    *value = parsed;
+                }
+            }
+        }
+
+        self.format = SESSION_FORMAT;
+        self.update = true;
+    }
+
+    /// Returns false once the Session Expired or outlived the absolute lifespan.
+    pub fn validate(&self, config: &SqlxSessionConfig) -> bool {
+        let now = Utc::now();
+
+        match config.absolute_lifespan {
+            Some(absolute) => self.expires >= now && add_lifespan(self.created_at, absolute) >= now,
+            None => self.expires >= now,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn validate_checks_absolute_lifespan() {
+        let config = SqlxSessionConfig::default().with_absolute_lifetime(Duration::hours(1));
+        let mut session = SQLxSessionData::new("id".into(), &config);
+        assert!(session.validate(&config));
+
+        // Expires is still ahead, e.g. Saved before the absolute lifespan was set.
+        session.created_at = Utc::now() - Duration::hours(2);
+        session.expires = Utc::now() + Duration::hours(1);
+        assert!(!session.validate(&config));
+        assert!(session.validate(&SqlxSessionConfig::default()));
+    }
+
+    #[test]
+    fn extend_clamps_to_absolute_lifespan() {
+        let config = SqlxSessionConfig::default().with_absolute_lifetime(Duration::hours(1));
+        let mut session = SQLxSessionData::new("id".into(), &SqlxSessionConfig::default());
+        session.expires = Utc::now() + Duration::days(30);
+        session.fixed_expiry = true;
+        session.update = false;
+
+        assert!(session.extend(&config));
+        assert!(session.expires <= session.created_at + Duration::hours(1));
+        assert!(session.update);
+
+        // Already within the absolute lifespan so nothing changes.
+        session.update = false;
+        assert!(!session.extend(&config));
+        assert!(!session.update);
+    }
+
+    #[test]
+    fn huge_lifespans_are_capped() {
+        let config = SqlxSessionConfig::default()
+            .with_lifetime(Duration::MAX)
+            .with_absolute_lifetime(Duration::MAX);
+        let mut session = SQLxSessionData::new("id".into(), &config);
+        assert!(session.validate(&config));
+
+        session.custom_lifespan = Some(Duration::MAX.num_seconds());
+        session.reset_expiry(&config);
+        session.extend(&config);
+
+        assert_eq!(session.expires, add_lifespan(Utc::now(), Duration::MAX));
+        assert!(session.validate(&config));
+        assert!(session.cookie_max_age().unwrap() > Duration::zero());
+    }
+
+    #[test]
+    fn upgrade_parses_old_values() {
+        let old = json!({
+            "data": {"user": "5", "name": "\"bob\"", "list": "[1,2]"},
+            "expires": Utc::now(),
+            "autoremove": Utc::now(),
+            "destroy": false,
+        });
+        let mut session: SQLxSessionData = serde_json::from_value(old).unwrap();
+        assert_eq!(session.format, 0);
+
+        session.upgrade();
+
+        assert_eq!(session.data["user"], json!(5));
+        assert_eq!(session.data["name"], json!("bob"));
+        assert_eq!(session.data["list"], json!([1, 2]));
+        assert_eq!(session.format, SESSION_FORMAT);
+        assert!(session.update);
+    }
+
+    #[test]
+    fn upgrade_keeps_current_values() {
+        let mut session = SQLxSessionData::new("id".into(), &SqlxSessionConfig::default());
+        session.data.insert("quoted".into(), json!("\"x\""));
+        session.data.insert("null".into(), json!("null"));
+        session.data.insert("number".into(), json!("5"));
+        session.update = false;
+
+        session.upgrade();
+
+        assert_eq!(session.data["quoted"], json!("\"x\""));
+        assert_eq!(session.data["null"], json!("null"));
+        assert_eq!(session.data["number"], json!("5"));
+        assert!(!session.update);
     }
 }
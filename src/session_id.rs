@@ -1,24 +1,56 @@
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
-use uuid::Uuid;
+
+/// Base64url alphabet, each character carries 6 bits of entropy.
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Shortest ID allowed, 22 characters gives 132 bits of entropy.
+pub(crate) const MIN_ID_LEN: usize = 22;
+
+/// Longest ID allowed, this matches the smallest ID column used by the Databases.
+pub(crate) const MAX_ID_LEN: usize = 128;
 
 ///This Contains the ID of the Session which is stored in a Cookie and in the Main SessionStore Hash
 /// to find their SessionData
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
-pub struct SQLxSessionID(pub Uuid);
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SQLxSessionID(pub String);
 
 impl SQLxSessionID {
-    pub fn new(uuid: Uuid) -> SQLxSessionID {
-        SQLxSessionID(uuid)
+    pub fn new(id: String) -> SQLxSessionID {
+        SQLxSessionID(id)
+    }
+
+    /// Generates a random ID of the given length using the thread's CSPRNG.
+    pub fn generate(len: usize) -> SQLxSessionID {
+        let mut rng = rand::thread_rng();
+
+        SQLxSessionID(
+            (0..len.clamp(MIN_ID_LEN, MAX_ID_LEN))
+                .map(|_| ALPHABET[rng.gen_range(0..ALPHABET.len())] as char)
+                .collect(),
+        )
+    }
+
+    /// Parses an ID sent by the client, returning None if it could not have been generated by us.
+    pub fn parse(id: &str) -> Option<SQLxSessionID> {
+        if id.len() < MIN_ID_LEN
+            || id.len() > MAX_ID_LEN
+            || !id.bytes().all(|byte| ALPHABET.contains(&byte))
+        {
+            return None;
+        }
+
+        Some(SQLxSessionID(id.to_string()))
     }
 
     pub fn inner(&self) -> String {
-        self.0.to_string()
+        self.0.clone()
     }
 }
 
 impl Display for SQLxSessionID {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.write_str(&self.0.to_string())
+        f.write_str(&self.0)
     }
 }
@@ -1,11 +1,16 @@
-use crate::{SQLxSessionData, SqlxSessionConfig};
-use anyhow::Error;
-use chrono::{DateTime, Duration, Utc};
+use crate::{
+    session_data::add_lifespan, SQLxSessionData, SQLxSessionSweeper, SessionDatabase, SessionError,
+    SqlxSessionConfig,
+};
+use chrono::{DateTime, Utc};
 use parking_lot::{Mutex, RwLock};
-use sqlx::{pool::PoolConnection, postgres::PgPool};
-use std::{collections::HashMap, sync::Arc};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicBool, Arc},
+};
 
-type Result<T = ()> = std::result::Result<T, Error>;
+type Result<T = ()> = std::result::Result<T, SessionError>;
 
 #[derive(Debug)]
 pub struct SQLxTimers {
@@ -13,12 +18,15 @@ pub struct SQLxTimers {
     pub last_database_expiry_sweep: DateTime<Utc>,
 }
 
-/// This stores the Postgresql Pool and the Main timers and a hash table that stores the SessionData.
+/// This stores the Database Pool and the Main timers and a hash table that stores the SessionData.
 /// It is also used to Initiate a Database Migrate, Cleanup, etc when used directly.
 #[derive(Clone, Debug)]
-pub struct SQLxSessionStore {
-    //move to layer creation
-    pub client: PgPool,
+pub struct SQLxSessionStore<T>
+where
+    T: SessionDatabase,
+{
+    /// Database Pool, Sessions are only kept in Memory when it is not persistent.
+    pub client: T,
     /// locked Hashmap containing UserID and their session data
     pub inner: Arc<RwLock<HashMap<String, Mutex<SQLxSessionData>>>>,
 
@@ -27,122 +35,133 @@ pub struct SQLxSessionStore {
 
     //move this to creation on layer.
     pub timers: Arc<RwLock<SQLxTimers>>,
+
+    /// Set while the background sweeper runs so Sessions are not also swept during requests.
+    pub(crate) sweeper_active: Arc<AtomicBool>,
 }
 
-impl SQLxSessionStore {
-    pub fn new(client: PgPool, config: SqlxSessionConfig) -> Self {
+impl<T> SQLxSessionStore<T>
+where
+    T: SessionDatabase,
+{
+    pub fn new(client: T, config: SqlxSessionConfig) -> Self {
         Self {
             client,
             inner: Default::default(),
-            config,
             timers: Arc::new(RwLock::new(SQLxTimers {
                 // the first expiry sweep is scheduled one lifetime from start-up
-                last_expiry_sweep: Utc::now() + Duration::hours(1),
+                last_expiry_sweep: add_lifespan(Utc::now(), config.memory_lifespan),
                 // the first expiry sweep is scheduled one lifetime from start-up
-                last_database_expiry_sweep: Utc::now() + Duration::hours(6),
+                last_database_expiry_sweep: add_lifespan(Utc::now(), config.lifespan),
             })),
+            config,
+            sweeper_active: Default::default(),
         }
     }
 
-    pub async fn migrate(&self) -> sqlx::Result<()> {
-        let mut conn = self.client.acquire().await?;
-        sqlx::query(&*self.substitute_table_name(
-            r#"
-            CREATE TABLE IF NOT EXISTS %%TABLE_NAME%% (
-                "id" VARCHAR NOT NULL PRIMARY KEY,
-                "expires" TIMESTAMP WITH TIME ZONE NULL,
-                "session" TEXT NOT NULL
-            )
-            "#,
-        ))
-        .execute(&mut conn)
-        .await?;
+    /// Returns true if Sessions are Persisted to a Database.
+    pub fn is_persistent(&self) -> bool {
+        self.client.is_persistent()
+    }
 
-        Ok(())
+    /// Checks if a Session should stay within Memory during a sweep.
+    /// Without a Database the Memory copy is all we have so it is kept till it Expires.
+    pub(crate) fn retain_in_memory(&self, session: &SQLxSessionData) -> bool {
+        if self.is_persistent() {
+            session.autoremove > Utc::now()
+        } else {
+            session.expires > Utc::now()
+        }
     }
 
-    fn substitute_table_name(&self, query: &str) -> String {
-        query.replace("%%TABLE_NAME%%", &self.config.table_name)
+    /// Spawns a background task that sweeps expired Sessions every `sweep_interval` from the config.
+    /// Returns None if no interval was set. Must be called within a Tokio runtime.
+    pub fn spawn_sweeper(&self) -> Option<SQLxSessionSweeper> {
+        let interval = self.config.sweep_interval?.to_std().ok()?;
+
+        Some(SQLxSessionSweeper::spawn(self.clone(), interval))
     }
 
-    async fn connection(&self) -> sqlx::Result<PoolConnection<sqlx::Postgres>> {
-        self.client.acquire().await
+    /// Removes expired Sessions from Memory and then from the Database.
+    pub async fn sweep(&self) -> Result {
+        self.inner
+            .write()
+            .retain(|_k, v| self.retain_in_memory(&v.lock()));
+        self.cleanup().await
     }
 
-    pub async fn cleanup(&self) -> sqlx::Result<()> {
-        let mut connection = self.connection().await?;
-        sqlx::query(&self.substitute_table_name("DELETE FROM %%TABLE_NAME%% WHERE expires < $1"))
-            .bind(Utc::now())
-            .execute(&mut connection)
-            .await?;
+    /// Returns the key the Session is stored under within the Database. When hashing is enabled
+    /// this is the hex encoded SHA-256 of the ID so a Database leak can not be used to hijack Sessions.
+    pub(crate) fn database_id(&self, id: &str) -> String {
+        if !self.config.hash_ids {
+            return id.to_string();
+        }
+
+        Sha256::digest(id.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
 
-        Ok(())
+    pub async fn migrate(&self) -> Result {
+        self.client.migrate(&self.config.table_name).await
     }
 
-    pub async fn count(&self) -> sqlx::Result<i64> {
-        let (count,) =
-            sqlx::query_as(&self.substitute_table_name("SELECT COUNT(*) FROM %%TABLE_NAME%%"))
-                .fetch_one(&mut self.connection().await?)
-                .await?;
+    pub async fn cleanup(&self) -> Result {
+        self.client.cleanup(&self.config.table_name).await
+    }
 
-        Ok(count)
+    pub async fn count(&self) -> Result<i64> {
+        if self.is_persistent() {
+            self.client.count(&self.config.table_name).await
+        } else {
+            Ok(self.inner.read().len() as i64)
+        }
     }
 
     pub async fn load_session(&self, cookie_value: String) -> Result<Option<SQLxSessionData>> {
-        let mut connection = self.connection().await?;
-
-        let result: Option<(String,)> = sqlx::query_as(&self.substitute_table_name(
-            "SELECT session FROM %%TABLE_NAME%% WHERE id = $1 AND (expires IS NULL OR expires > $2)"
-        ))
-        .bind(&cookie_value)
-        .bind(Utc::now())
-        .fetch_optional(&mut connection)
-        .await?;
-
-        Ok(result
-            .map(|(session,)| serde_json::from_str(&session))
-            .transpose()?)
+        let result = self
+            .client
+            .load(&self.database_id(&cookie_value), &self.config.table_name)
+            .await?;
+
+        let mut session: Option<SQLxSessionData> = result
+            .map(|session| self.config.codec.decode(&session))
+            .transpose()?;
+
+        // The ID is not Saved within the Session so a Database leak does not expose it.
+        if let Some(session) = &mut session {
+            session.id = cookie_value;
+            session.upgrade();
+        }
+
+        Ok(session)
     }
 
-    pub async fn store_session(&self, session: SQLxSessionData) -> Result<()> {
-        let id = session.id.clone();
-        let string = serde_json::to_string(&session)?;
-        let mut connection = self.connection().await?;
-
-        sqlx::query(&self.substitute_table_name(
-            r#"
-            INSERT INTO %%TABLE_NAME%%
-              (id, session, expires) SELECT $1, $2, $3
-            ON CONFLICT(id) DO UPDATE SET
-              expires = EXCLUDED.expires,
-              session = EXCLUDED.session
-            "#,
-        ))
-        .bind(&id)
-        .bind(&string)
-        .bind(&session.expires)
-        .execute(&mut connection)
-        .await?;
-
-        Ok(())
+    pub async fn store_session(&self, session: SQLxSessionData) -> Result {
+        if !self.is_persistent() {
+            return Ok(());
+        }
+
+        let bytes = self.config.codec.encode(&session)?;
+
+        self.client
+            .store(
+                &self.database_id(&session.id),
+                &bytes,
+                session.expires,
+                &self.config.table_name,
+            )
+            .await
     }
 
     pub async fn destroy_session(&self, id: &str) -> Result {
-        let mut connection = self.connection().await?;
-        sqlx::query(&self.substitute_table_name("DELETE FROM %%TABLE_NAME%% WHERE id = $1"))
-            .bind(&id)
-            .execute(&mut connection)
-            .await?;
-
-        Ok(())
+        self.client
+            .delete(&self.database_id(id), &self.config.table_name)
+            .await
     }
 
     pub async fn clear_store(&self) -> Result {
-        let mut connection = self.connection().await?;
-        sqlx::query(&self.substitute_table_name("TRUNCATE %%TABLE_NAME%%"))
-            .execute(&mut connection)
-            .await?;
-
-        Ok(())
+        self.client.clear(&self.config.table_name).await
     }
 }
@@ -0,0 +1,52 @@
+use crate::{SQLxSessionStore, SessionDatabase};
+use std::sync::atomic::Ordering;
+use tokio::{
+    sync::oneshot,
+    task::JoinHandle,
+    time::{interval_at, Instant},
+};
+
+///Handle to the background task that sweeps expired Sessions from Memory and the Database.
+/// Dropping the handle leaves the task running, call stop to end it on shutdown.
+#[derive(Debug)]
+pub struct SQLxSessionSweeper {
+    shutdown: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl SQLxSessionSweeper {
+    /// Spawns the sweeper onto the Tokio runtime, it runs once every interval.
+    pub(crate) fn spawn<T>(store: SQLxSessionStore<T>, interval: std::time::Duration) -> Self
+    where
+        T: SessionDatabase,
+    {
+        let (shutdown, mut stopped) = oneshot::channel();
+        store.sweeper_active.store(true, Ordering::SeqCst);
+
+        let handle = tokio::spawn(async move {
+            let mut interval = interval_at(Instant::now() + interval, interval);
+
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        if let Err(err) = store.sweep().await {
+                            tracing::error!("Failed to sweep Sessions: {}", err);
+                        }
+                    }
+                    _ = &mut stopped => break,
+                }
+            }
+
+            // Requests take over sweeping again once stopped.
+            store.sweeper_active.store(false, Ordering::SeqCst);
+        });
+
+        Self { shutdown, handle }
+    }
+
+    /// Stops the sweeper and waits for any sweep in progress to finish.
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.handle.await;
+    }
+}
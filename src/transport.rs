@@ -0,0 +1,444 @@
+use crate::{SQLxSessionID, SQLxSessionTransport, SqlxSessionConfig};
+use chrono::Duration;
+use cookie::{Cookie, CookieJar};
+use http::{
+    header::{AUTHORIZATION, COOKIE, SET_COOKIE},
+    HeaderValue, Request, Response,
+};
+
+/// Carries the Session ID between the client and the Manager for a single request.
+/// Depending on the config it is read from and written to the cookie jar, the Session header or both.
+pub(crate) struct Transport<'a> {
+    config: &'a SqlxSessionConfig,
+    /// Cookies sent by the client along with any we add or remove.
+    cookies: Option<CookieJar>,
+    /// Session header value sent by the client.
+    header: Option<String>,
+    /// Encoded Session ID to send back with the Response.
+    issued: Option<String>,
+}
+
+impl<'a> Transport<'a> {
+    pub(crate) fn new<B>(config: &'a SqlxSessionConfig, req: &Request<B>) -> Self {
+        let cookies = if config.transport != SQLxSessionTransport::Header {
+            let mut jar = CookieJar::new();
+
+            for value in req.headers().get_all(COOKIE) {
+                let value = match value.to_str() {
+                    Ok(value) => value,
+                    Err(_) => continue,
+                };
+
+                for cookie in value.split(';').map(str::trim) {
+                    if let Ok(cookie) = Cookie::parse_encoded(cookie.to_string()) {
+                        jar.add_original(cookie);
+                    }
+                }
+            }
+
+            Some(jar)
+        } else {
+            None
+        };
+
+        let header = if config.transport != SQLxSessionTransport::Cookie {
+            req.headers()
+                .get(&config.header_name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| {
+                    if config.header_name != AUTHORIZATION {
+                        return Some(value.trim().to_string());
+                    }
+
+                    // Only Bearer tokens carry the Session ID, other schemes are left to the application.
+                    let (scheme, token) = value.trim().split_once(' ')?;
+                    scheme
+                        .eq_ignore_ascii_case("Bearer")
+                        .then(|| token.trim().to_string())
+                })
+        } else {
+            None
+        };
+
+        Self {
+            config,
+            cookies,
+            header,
+            issued: None,
+        }
+    }
+
+    /// Gets the Session ID sent by the client, the header takes priority over the cookie and
+    /// the cookie is used when the header is invalid. Returns None when no ID was sent and
+    /// Some(None) when none of the sent IDs were valid. Values encrypted with an old key are
+    /// issued again with the current one.
+    pub(crate) fn get(&mut self) -> Option<Option<SQLxSessionID>> {
+        let cookie = self
+            .cookies
+            .as_ref()
+            .and_then(|cookies| cookies.get(&self.config.cookie_name))
+            .map(|cookie| cookie.value().to_string());
+
+        let values: Vec<String> = self.header.iter().cloned().chain(cookie).collect();
+
+        if values.is_empty() {
+            return None;
+        }
+
+        for value in values {
+            let (value, rotated) = match decode(self.config, value) {
+                Some(decoded) => decoded,
+                None => continue,
+            };
+
+            if let Some(id) = SQLxSessionID::parse(&value) {
+                if rotated {
+                    self.issued = Some(encode(self.config, value));
+                }
+
+                return Some(Some(id));
+            }
+        }
+
+        Some(None)
+    }
+
+    /// Issues the Session ID to the client once the Response is ready.
+    pub(crate) fn issue(&mut self, id: &SQLxSessionID) {
+        self.issued = Some(encode(self.config, id.to_string()));
+    }
+
+    /// Drops any issued Session ID so nothing is sent to the client.
+    pub(crate) fn cancel(&mut self) {
+        self.issued = None;
+    }
+
+    /// Expires the Session cookie, the Path and Domain must match for the browser to remove it.
+    pub(crate) fn remove(&mut self) {
+        if let Some(cookies) = &mut self.cookies {
+            let mut cookie = Cookie::named(self.config.cookie_name.clone());
+            cookie.set_path(self.config.cookie_path.clone());
+
+            if let Some(domain) = &self.config.cookie_domain {
+                cookie.set_domain(domain.clone());
+            }
+
+            cookies.remove(cookie);
+        }
+
+        self.issued = None;
+    }
+
+    /// Adds any changed cookies to the Response and sets the Session header if an ID was issued.
+    /// Without a Max-Age the cookie only lasts till the browser is closed.
+    pub(crate) fn apply<B>(&mut self, response: &mut Response<B>, max_age: Option<Duration>) {
+        if let (Some(cookies), Some(value)) = (&mut self.cookies, &self.issued) {
+            cookies.add(build_cookie(self.config, value.clone(), max_age));
+        }
+
+        if let Some(cookies) = &self.cookies {
+            for cookie in cookies.delta() {
+                if let Ok(value) = HeaderValue::from_str(&cookie.encoded().to_string()) {
+                    response.headers_mut().append(SET_COOKIE, value);
+                }
+            }
+        }
+
+        if self.config.transport == SQLxSessionTransport::Cookie {
+            return;
+        }
+
+        let value = match &self.issued {
+            Some(value) if self.config.header_name == AUTHORIZATION => format!("Bearer {}", value),
+            Some(value) => value.clone(),
+            None => return,
+        };
+
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response
+                .headers_mut()
+                .insert(self.config.header_name.clone(), value);
+        }
+    }
+}
+
+/// Creates the Session cookie with the attributes from the config.
+fn build_cookie(
+    config: &SqlxSessionConfig,
+    value: String,
+    max_age: Option<Duration>,
+) -> Cookie<'static> {
+    let mut cookie = Cookie::new(config.cookie_name.clone(), value);
+    cookie.set_path(config.cookie_path.clone());
+    cookie.set_secure(config.cookie_secure);
+    cookie.set_http_only(config.cookie_http_only);
+    cookie.set_same_site(config.cookie_same_site);
+
+    if let Some(max_age) = max_age {
+        cookie.set_max_age(time::Duration::seconds(max_age.num_seconds()));
+    }
+
+    if let Some(domain) = &config.cookie_domain {
+        cookie.set_domain(domain.clone());
+    }
+
+    cookie
+}
+
+/// Encrypts or Signs the value if a key was set.
+fn encode(config: &SqlxSessionConfig, value: String) -> String {
+    let mut jar = CookieJar::new();
+    let cookie = Cookie::new(config.cookie_name.clone(), value);
+
+    if let Some(key) = &config.private_key {
+        jar.private_mut(&key.0).add(cookie);
+    } else if let Some(key) = &config.signing_key {
+        jar.signed_mut(&key.0).add(cookie);
+    } else {
+        jar.add(cookie);
+    }
+
+    jar.get(&config.cookie_name)
+        .map(|cookie| cookie.value().to_string())
+        .unwrap_or_default()
+}
+
+/// Decrypts or Verifies the value if a key was set. Returns the value and if it was
+/// decrypted using an old key.
+fn decode(config: &SqlxSessionConfig, value: String) -> Option<(String, bool)> {
+    let mut jar = CookieJar::new();
+    jar.add_original(Cookie::new(config.cookie_name.clone(), value));
+
+    if let Some(key) = &config.private_key {
+        if let Some(cookie) = jar.private(&key.0).get(&config.cookie_name) {
+            return Some((cookie.value().to_string(), false));
+        }
+
+        return config.old_private_keys.iter().find_map(|old_key| {
+            jar.private(&old_key.0)
+                .get(&config.cookie_name)
+                .map(|cookie| (cookie.value().to_string(), true))
+        });
+    }
+
+    match &config.signing_key {
+        Some(key) => jar
+            .signed(&key.0)
+            .get(&config.cookie_name)
+            .map(|cookie| (cookie.value().to_string(), false)),
+        None => jar
+            .get(&config.cookie_name)
+            .map(|cookie| (cookie.value().to_string(), false)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cookie::Key;
+
+    /// Changes a character within the value so it no longer matches its signature or tag.
+    fn tamper(value: &str, index: usize) -> String {
+        value
+            .char_indices()
+            .map(|(i, c)| match (i == index, c) {
+                (true, 'A') => 'B',
+                (true, _) => 'A',
+                (false, c) => c,
+            })
+            .collect()
+    }
+
+    fn request(config: &SqlxSessionConfig, value: &str) -> Request<()> {
+        let cookie = Cookie::new(config.cookie_name.clone(), value.to_string());
+
+        Request::builder()
+            .header(COOKIE, cookie.encoded().to_string())
+            .body(())
+            .unwrap()
+    }
+
+    fn get_id(
+        config: &SqlxSessionConfig,
+        header: Option<&str>,
+        cookie: Option<&str>,
+    ) -> Option<Option<SQLxSessionID>> {
+        let mut req = Request::builder();
+
+        if let Some(header) = header {
+            req = req.header(config.header_name.clone(), header);
+        }
+
+        if let Some(cookie) = cookie {
+            req = req.header(
+                COOKIE,
+                Cookie::new(config.cookie_name.clone(), cookie)
+                    .encoded()
+                    .to_string(),
+            );
+        }
+
+        let req = req.body(()).unwrap();
+        Transport::new(config, &req).get()
+    }
+
+    fn bearer_config() -> SqlxSessionConfig {
+        SqlxSessionConfig::default()
+            .with_transport(SQLxSessionTransport::CookieAndHeader)
+            .with_header_name(AUTHORIZATION)
+    }
+
+    #[test]
+    fn bearer_header() {
+        let config = bearer_config();
+        let id = SQLxSessionID::generate(32);
+
+        let header = format!("Bearer {}", id);
+        assert_eq!(get_id(&config, Some(&header), None), Some(Some(id.clone())));
+
+        let header = format!("bearer  {} ", id);
+        assert_eq!(get_id(&config, Some(&header), None), Some(Some(id)));
+    }
+
+    #[test]
+    fn other_schemes_are_ignored() {
+        let config = bearer_config();
+        let id = SQLxSessionID::generate(32);
+        let cookie = SQLxSessionID::generate(32);
+
+        let header = format!("Basic {}", id);
+        assert_eq!(get_id(&config, Some(&header), None), None);
+        assert_eq!(get_id(&config, Some(id.0.as_str()), None), None);
+        assert_eq!(
+            get_id(&config, Some(&header), Some(cookie.0.as_str())),
+            Some(Some(cookie))
+        );
+    }
+
+    #[test]
+    fn header_takes_priority_over_cookie() {
+        let id = SQLxSessionID::generate(32);
+        let cookie = SQLxSessionID::generate(32);
+
+        let header = format!("Bearer {}", id);
+        assert_eq!(
+            get_id(&bearer_config(), Some(&header), Some(cookie.0.as_str())),
+            Some(Some(id.clone()))
+        );
+
+        // Other header names carry the bare ID.
+        let config =
+            SqlxSessionConfig::default().with_transport(SQLxSessionTransport::CookieAndHeader);
+        assert_eq!(
+            get_id(&config, Some(id.0.as_str()), Some(cookie.0.as_str())),
+            Some(Some(id))
+        );
+    }
+
+    #[test]
+    fn invalid_header_falls_back_to_cookie() {
+        let config = bearer_config();
+        let cookie = SQLxSessionID::generate(32);
+
+        assert_eq!(
+            get_id(&config, Some("Bearer not-an-id!"), Some(cookie.0.as_str())),
+            Some(Some(cookie))
+        );
+        assert_eq!(get_id(&config, Some("Bearer not-an-id!"), None), Some(None));
+        assert_eq!(
+            get_id(&config, Some("Bearer not-an-id!"), Some("bad!")),
+            Some(None)
+        );
+    }
+
+    #[test]
+    fn plain_round_trip() {
+        let config = SqlxSessionConfig::default();
+        let id = SQLxSessionID::generate(32).to_string();
+
+        assert_eq!(encode(&config, id.clone()), id);
+        assert_eq!(decode(&config, id.clone()), Some((id, false)));
+    }
+
+    #[test]
+    fn signed_round_trip() {
+        let config = SqlxSessionConfig::default().with_signing_key(Key::generate());
+        let id = SQLxSessionID::generate(32).to_string();
+        let encoded = encode(&config, id.clone());
+
+        assert_ne!(encoded, id);
+        assert_eq!(decode(&config, encoded.clone()), Some((id.clone(), false)));
+
+        // Tampered signature, tampered ID, an unsigned ID and a different key are all rejected.
+        assert_eq!(decode(&config, tamper(&encoded, 5)), None);
+        assert_eq!(decode(&config, tamper(&encoded, encoded.len() - 5)), None);
+        assert_eq!(decode(&config, id), None);
+
+        let other = SqlxSessionConfig::default().with_signing_key(Key::generate());
+        assert_eq!(decode(&other, encoded), None);
+    }
+
+    #[test]
+    fn private_current_key() {
+        let config = SqlxSessionConfig::default().with_private_key(Key::generate());
+        let id = SQLxSessionID::generate(32).to_string();
+        let encoded = encode(&config, id.clone());
+
+        assert!(!encoded.contains(&id));
+        assert_eq!(decode(&config, encoded.clone()), Some((id.clone(), false)));
+
+        // Already encrypted with the current key so it is not issued again.
+        let req = request(&config, &encoded);
+        let mut transport = Transport::new(&config, &req);
+        assert_eq!(transport.get(), Some(Some(SQLxSessionID(id))));
+        assert_eq!(transport.issued, None);
+    }
+
+    #[test]
+    fn private_old_key_is_reissued() {
+        let old_key = Key::generate();
+        let old = SqlxSessionConfig::default().with_private_key(old_key.clone());
+        let config = SqlxSessionConfig::default()
+            .with_private_key(Key::generate())
+            .with_old_private_keys(vec![Key::generate(), old_key]);
+        let id = SQLxSessionID::generate(32).to_string();
+        let encoded = encode(&old, id.clone());
+
+        assert_eq!(decode(&config, encoded.clone()), Some((id.clone(), true)));
+
+        let req = request(&config, &encoded);
+        let mut transport = Transport::new(&config, &req);
+        assert_eq!(transport.get(), Some(Some(SQLxSessionID(id.clone()))));
+
+        let issued = transport
+            .issued
+            .expect("cookie issued with the current key");
+        assert_eq!(decode(&config, issued), Some((id, false)));
+    }
+
+    #[test]
+    fn private_tampered_is_rejected() {
+        let old_key = Key::generate();
+        let config = SqlxSessionConfig::default()
+            .with_private_key(Key::generate())
+            .with_old_private_keys(vec![old_key.clone()]);
+        let id = SQLxSessionID::generate(32).to_string();
+
+        let encoded = encode(&config, id.clone());
+        assert_eq!(decode(&config, tamper(&encoded, 5)), None);
+        assert_eq!(decode(&config, tamper(&encoded, 20)), None);
+        assert_eq!(decode(&config, id.clone()), None);
+
+        let old = SqlxSessionConfig::default().with_private_key(old_key);
+        assert_eq!(decode(&config, tamper(&encode(&old, id.clone()), 20)), None);
+
+        // Keys that were dropped from the old keys are no longer accepted.
+        let dropped = SqlxSessionConfig::default().with_private_key(Key::generate());
+        assert_eq!(decode(&config, encode(&dropped, id.clone())), None);
+
+        let req = request(&config, &tamper(&encoded, 20));
+        let mut transport = Transport::new(&config, &req);
+        assert_eq!(transport.get(), Some(None));
+        assert_eq!(transport.issued, None);
+    }
+}
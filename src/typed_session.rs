@@ -0,0 +1,106 @@
+use crate::{SQLxSession, SessionDatabase};
+use axum::{
+    async_trait,
+    extract::{FromRequest, RequestParts},
+    http,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::ops::{Deref, DerefMut};
+
+///Sets the Session key a Typed Session's data is stored under. It must stay the same between
+/// releases and be unique per type, otherwise Saved Sessions can no longer be loaded.
+pub trait SQLxSessionKey {
+    const KEY: &'static str;
+}
+
+///This is a Typed view of the Session. The whole struct is loaded from the Session when extracted
+/// and written back once dropped or committed, it is stored under its `SQLxSessionKey`.
+#[derive(Debug)]
+pub struct SQLxTypedSession<T, D>
+where
+    T: SessionDatabase,
+    D: SQLxSessionKey + Serialize + DeserializeOwned + Default,
+{
+    session: SQLxSession<T>,
+    data: D,
+    /// Serialized data as last loaded or committed, used to skip unchanged writes.
+    committed: Value,
+}
+
+/// this auto pulls the typed data from the SQLxSession when added by the Session managers call
+/// if for some reason the Session Manager did not run this will Error.
+#[async_trait]
+impl<B, T, D> FromRequest<B> for SQLxTypedSession<T, D>
+where
+    B: Send,
+    T: SessionDatabase,
+    D: SQLxSessionKey + Serialize + DeserializeOwned + Default + Send,
+{
+    type Rejection = (http::StatusCode, &'static str);
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let session = SQLxSession::<T>::from_request(req).await?;
+        let data = session.get(D::KEY).unwrap_or_default();
+        let committed = serde_json::to_value(&data).unwrap_or(Value::Null);
+
+        Ok(Self {
+            session,
+            data,
+            committed,
+        })
+    }
+}
+
+impl<T, D> SQLxTypedSession<T, D>
+where
+    T: SessionDatabase,
+    D: SQLxSessionKey + Serialize + DeserializeOwned + Default,
+{
+    /// Writes the data back to the Session if it changed since it was loaded or last committed.
+    pub fn commit(&mut self) {
+        let value = serde_json::to_value(&self.data).unwrap_or(Value::Null);
+
+        if value != self.committed {
+            self.session.set(D::KEY, &self.data);
+            self.committed = value;
+        }
+    }
+
+    /// Returns the underlying Session, e.g. to destroy or renew it.
+    pub fn session(&self) -> &SQLxSession<T> {
+        &self.session
+    }
+}
+
+impl<T, D> Deref for SQLxTypedSession<T, D>
+where
+    T: SessionDatabase,
+    D: SQLxSessionKey + Serialize + DeserializeOwned + Default,
+{
+    type Target = D;
+
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<T, D> DerefMut for SQLxTypedSession<T, D>
+where
+    T: SessionDatabase,
+    D: SQLxSessionKey + Serialize + DeserializeOwned + Default,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
+
+impl<T, D> Drop for SQLxTypedSession<T, D>
+where
+    T: SessionDatabase,
+    D: SQLxSessionKey + Serialize + DeserializeOwned + Default,
+{
+    fn drop(&mut self) {
+        self.commit();
+    }
+}